@@ -0,0 +1,154 @@
+// Real-time streaming playback via cpal.
+//
+// Drives a `Ym2151` live to the default output device: a worker thread applies
+// register writes queued from the control thread and feeds `generate_sample()`
+// results through a ring buffer into the audio callback. The same sample stream
+// is tee'd into an optional WAV recorder so users can capture what they hear.
+// This is the shared foundation for both the MIDI and VGM players.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::mpsc::{self, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use crate::Ym2151;
+
+// Generate samples in blocks to keep the register-write latency low.
+const BLOCK: usize = 256;
+// Keep roughly this many stereo frames buffered ahead of the callback.
+const TARGET: usize = 4096;
+
+// A register write queued from the control thread to the synth worker.
+struct RegisterWrite {
+    address: u8,
+    data: u8,
+}
+
+// Shared stereo sample queue between the synth worker and the audio callback.
+type RingBuffer = Arc<Mutex<VecDeque<(i16, i16)>>>;
+
+// Streams a `Ym2151` to the default audio device until dropped.
+pub struct AudioPlayer {
+    _stream: cpal::Stream,
+    writes: Sender<RegisterWrite>,
+}
+
+impl AudioPlayer {
+    // Start streaming `ym` to the default output device. If `record_path` is
+    // given, a stereo 16-bit WAV copy of the output is written there.
+    pub fn start(ym: Ym2151, record_path: Option<&str>) -> Result<Self, String> {
+        let host = cpal::default_host();
+        let device = host
+            .default_output_device()
+            .ok_or_else(|| "no default output device".to_string())?;
+        let config = device
+            .default_output_config()
+            .map_err(|e| e.to_string())?;
+        let channels = config.channels() as usize;
+        let sample_rate = config.sample_rate().0;
+
+        // The callback consumes one ring frame per device output frame, so the
+        // synth must generate at the device rate. Retarget `ym` before streaming
+        // or playback is pitch/speed-wrong on any non-default device.
+        let mut ym = ym;
+        ym.set_output_rate(sample_rate);
+
+        let ring: RingBuffer = Arc::new(Mutex::new(VecDeque::with_capacity(TARGET * 2)));
+        let (writes, write_rx) = mpsc::channel::<RegisterWrite>();
+
+        let recorder = match record_path {
+            Some(path) => Some(open_recorder(path, sample_rate)?),
+            None => None,
+        };
+
+        // Worker thread owns the chip, applies queued writes and tops up the
+        // ring buffer (teeing into the recorder on the way).
+        let ring_worker = ring.clone();
+        thread::spawn(move || {
+            let mut ym = ym;
+            let mut recorder = recorder;
+            loop {
+                match write_rx.try_recv() {
+                    Ok(w) => {
+                        ym.write_register(w.address, w.data);
+                        continue;
+                    }
+                    Err(TryRecvError::Disconnected) => break,
+                    Err(TryRecvError::Empty) => {}
+                }
+
+                let len = ring_worker.lock().unwrap().len();
+                if len < TARGET {
+                    let block = ym.generate_samples(BLOCK);
+                    if let Some(writer) = recorder.as_mut() {
+                        for (l, r) in &block {
+                            let _ = writer.write_sample(*l);
+                            let _ = writer.write_sample(*r);
+                        }
+                    }
+                    ring_worker.lock().unwrap().extend(block);
+                } else {
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+
+            if let Some(writer) = recorder.take() {
+                let _ = writer.finalize();
+            }
+        });
+
+        let ring_cb = ring.clone();
+        let stream = device
+            .build_output_stream(
+                &config.into(),
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+                    let mut ring = ring_cb.lock().unwrap();
+                    for frame in data.chunks_mut(channels) {
+                        let (l, r) = ring.pop_front().unwrap_or((0, 0));
+                        if channels >= 2 {
+                            frame[0] = l as f32 / 32768.0;
+                            frame[1] = r as f32 / 32768.0;
+                            for s in frame.iter_mut().skip(2) {
+                                *s = 0.0;
+                            }
+                        } else if let Some(s) = frame.get_mut(0) {
+                            *s = (l as f32 + r as f32) / 2.0 / 32768.0;
+                        }
+                    }
+                },
+                move |err| eprintln!("audio stream error: {}", err),
+                None,
+            )
+            .map_err(|e| e.to_string())?;
+
+        stream.play().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            _stream: stream,
+            writes,
+        })
+    }
+
+    // Queue a register write to be applied on the synth worker thread.
+    pub fn write_register(&self, address: u8, data: u8) {
+        let _ = self.writes.send(RegisterWrite { address, data });
+    }
+}
+
+fn open_recorder(
+    path: &str,
+    sample_rate: u32,
+) -> Result<hound::WavWriter<BufWriter<File>>, String> {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    hound::WavWriter::create(path, spec).map_err(|e| e.to_string())
+}
@@ -0,0 +1,102 @@
+// Configurable output filtering for the sample path.
+//
+// Two first-order IIR filters applied to the raw Nuked-OPM output before the
+// `>>5` scale/clamp, mirroring the DC-removal high-pass and treble low-pass of
+// real hardware output stages. Each filter carries one channel of state, so the
+// `Ym2151` keeps a separate instance per L/R output.
+
+use std::f32::consts::PI;
+
+// Default cutoffs chosen to reproduce a ~0.996 high-pass and ~0.816 low-pass
+// coefficient at 44100 Hz.
+const DEFAULT_HP_HZ: f32 = 28.1;
+const DEFAULT_LP_HZ: f32 = 11876.0;
+
+// One-pole high-pass (DC blocker): y[n] = a*(y[n-1] + x[n] - x[n-1]).
+#[derive(Clone, Copy, Default)]
+pub struct HighPass {
+    x_prev: f32,
+    y_prev: f32,
+}
+
+impl HighPass {
+    pub fn process(&mut self, a: f32, x: f32) -> f32 {
+        let y = a * (self.y_prev + x - self.x_prev);
+        self.x_prev = x;
+        self.y_prev = y;
+        y
+    }
+}
+
+// One-pole low-pass: y[n] = y[n-1] + b*(x[n] - y[n-1]).
+#[derive(Clone, Copy, Default)]
+pub struct LowPass {
+    y_prev: f32,
+}
+
+impl LowPass {
+    pub fn process(&mut self, b: f32, x: f32) -> f32 {
+        let y = self.y_prev + b * (x - self.y_prev);
+        self.y_prev = y;
+        y
+    }
+}
+
+fn hp_coeff(cutoff_hz: f32, sample_rate: u32) -> f32 {
+    (1.0 - 2.0 * PI * cutoff_hz / sample_rate as f32).clamp(0.0, 1.0)
+}
+
+fn lp_coeff(cutoff_hz: f32, sample_rate: u32) -> f32 {
+    (1.0 - (-2.0 * PI * cutoff_hz / sample_rate as f32).exp()).clamp(0.0, 1.0)
+}
+
+// Selectable filters on the `Ym2151` sample path.
+pub struct FilterConfig {
+    pub high_pass: bool,
+    pub low_pass: bool,
+    hp_hz: f32,
+    lp_hz: f32,
+    a: f32,
+    b: f32,
+}
+
+impl FilterConfig {
+    // DC blocker enabled, treble low-pass disabled, coefficients for 44100 Hz.
+    pub fn new() -> Self {
+        Self::with_cutoffs(DEFAULT_HP_HZ, DEFAULT_LP_HZ, 44100)
+    }
+
+    // Build from cutoff frequencies (Hz) for a given output sample rate. The
+    // high-pass starts enabled and the low-pass disabled whatever the cutoffs;
+    // the `high_pass` / `low_pass` toggles change that.
+    pub fn with_cutoffs(high_pass_hz: f32, low_pass_hz: f32, sample_rate: u32) -> Self {
+        Self {
+            high_pass: true,
+            low_pass: false,
+            hp_hz: high_pass_hz,
+            lp_hz: low_pass_hz,
+            a: hp_coeff(high_pass_hz, sample_rate),
+            b: lp_coeff(low_pass_hz, sample_rate),
+        }
+    }
+
+    // Recompute coefficients for a new output sample rate.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.a = hp_coeff(self.hp_hz, sample_rate);
+        self.b = lp_coeff(self.lp_hz, sample_rate);
+    }
+
+    pub(crate) fn hp_coeff(&self) -> f32 {
+        self.a
+    }
+
+    pub(crate) fn lp_coeff(&self) -> f32 {
+        self.b
+    }
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
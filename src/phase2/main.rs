@@ -1,4 +1,12 @@
-use hound;
+mod vgm;
+mod voice;
+mod midi;
+mod filter;
+mod audio;
+mod output;
+
+use filter::{FilterConfig, HighPass, LowPass};
+use output::OutputSpec;
 
 // FFI bindings for Nuked-OPM
 // The opm_t structure is 1396 bytes
@@ -16,10 +24,13 @@ extern "C" {
     fn OPM_SetIC(chip: *mut OpmChip, ic: u8);
 }
 
-// Sample rate for WAV output
+// Default sample rate for WAV output
 const SAMPLE_RATE: u32 = 44100;
-// Number of chip clock cycles per audio sample
+// Default number of chip clock cycles per native chip sample
 const CLOCKS_PER_SAMPLE: usize = 64;
+// Default emulated chip clock: one native sample every CLOCKS_PER_SAMPLE cycles
+// at the default output rate, i.e. 44100 * 64.
+const CHIP_CLOCK: u32 = SAMPLE_RATE * CLOCKS_PER_SAMPLE as u32;
 // Number of clock cycles between register writes (for chip processing)
 const CLOCKS_BETWEEN_WRITES: usize = 10;
 // Calculate the number of chip cycles for 10ms
@@ -28,10 +39,35 @@ const CYCLES_10MS: usize = ((SAMPLE_RATE as f64 * 0.01) * CLOCKS_PER_SAMPLE as f
 
 struct Ym2151 {
     chip: Box<OpmChip>,
+    filter: FilterConfig,
+    // Per-channel filter state, indexed [0] = left, [1] = right.
+    high_pass: [HighPass; 2],
+    low_pass: [LowPass; 2],
+    // Requested output sample rate.
+    sample_rate: u32,
+    // Emulated chip clock in Hz, kept so the output rate can be retargeted
+    // (e.g. to a live device's rate) without losing the chip configuration.
+    chip_clock: u32,
+    // Chip clock cycles consumed per native chip sample.
+    clocks_per_sample: usize,
+    // Linear resampler state decoupling the native chip rate from the output
+    // rate. `step` is how many native samples advance per output sample.
+    resample_step: f64,
+    resample_pos: f64,
+    resample_prev: (f32, f32),
+    resample_curr: (f32, f32),
+    resample_primed: bool,
 }
 
 impl Ym2151 {
     fn new() -> Self {
+        Self::with_rates(SAMPLE_RATE, CHIP_CLOCK)
+    }
+
+    // Create a chip rendering at `sample_rate`, running the emulation at
+    // `chip_clock`. The resampler interpolates the chip's native rate
+    // (`chip_clock` / CLOCKS_PER_SAMPLE) to the requested output rate.
+    fn with_rates(sample_rate: u32, chip_clock: u32) -> Self {
         let mut chip = Box::new(OpmChip {
             _data: [0; 1400],
         });
@@ -56,9 +92,47 @@ impl Ym2151 {
             }
         }
         
-        Self { chip }
+        let clocks_per_sample = CLOCKS_PER_SAMPLE;
+        let native_rate = chip_clock as f64 / clocks_per_sample as f64;
+
+        let mut filter = FilterConfig::new();
+        filter.set_sample_rate(sample_rate);
+
+        Self {
+            chip,
+            filter,
+            high_pass: [HighPass::default(); 2],
+            low_pass: [LowPass::default(); 2],
+            sample_rate,
+            chip_clock,
+            clocks_per_sample,
+            resample_step: native_rate / sample_rate as f64,
+            resample_pos: 0.0,
+            resample_prev: (0.0, 0.0),
+            resample_curr: (0.0, 0.0),
+            resample_primed: false,
+        }
     }
-    
+
+    // Retarget the output sample rate, recomputing the resampler step and the
+    // filter coefficients. Used before streaming to a device whose rate differs
+    // from the render default; the chip keeps running at its configured clock.
+    fn set_output_rate(&mut self, sample_rate: u32) {
+        let native_rate = self.chip_clock as f64 / self.clocks_per_sample as f64;
+        self.sample_rate = sample_rate;
+        self.resample_step = native_rate / sample_rate as f64;
+        self.resample_pos = 0.0;
+        self.resample_primed = false;
+        self.filter.set_sample_rate(sample_rate);
+    }
+
+    // Replace the output filter configuration (DC blocker + optional low-pass),
+    // recomputing its coefficients for the current output rate.
+    fn set_filter(&mut self, mut filter: FilterConfig) {
+        filter.set_sample_rate(self.sample_rate);
+        self.filter = filter;
+    }
+
     fn write_register(&mut self, address: u8, data: u8) {
         unsafe {
             let mut output = [0i32; 2];
@@ -96,14 +170,16 @@ impl Ym2151 {
         }
     }
     
-    fn generate_sample(&mut self) -> (i16, i16) {
+    // Clock the chip for one native sample (CLOCKS_PER_SAMPLE cycles) and return
+    // the full-precision stereo output.
+    fn clock_native(&mut self) -> (f32, f32) {
         let mut output = [0i32; 2];
         let mut sh1 = 0u8;
         let mut sh2 = 0u8;
         let mut so = 0u8;
-        
+
         unsafe {
-            for _ in 0..CLOCKS_PER_SAMPLE {
+            for _ in 0..self.clocks_per_sample {
                 OPM_Clock(
                     self.chip.as_mut(),
                     output.as_mut_ptr(),
@@ -113,15 +189,75 @@ impl Ym2151 {
                 );
             }
         }
-        
+
+        (output[0] as f32, output[1] as f32)
+    }
+
+    // Pull one output-rate sample, linearly interpolating between the native
+    // chip samples that bracket the current fractional position.
+    fn next_resampled(&mut self) -> (f32, f32) {
+        if !self.resample_primed {
+            self.resample_prev = self.clock_native();
+            self.resample_curr = self.clock_native();
+            self.resample_primed = true;
+        }
+
+        while self.resample_pos >= 1.0 {
+            self.resample_prev = self.resample_curr;
+            self.resample_curr = self.clock_native();
+            self.resample_pos -= 1.0;
+        }
+
+        let frac = self.resample_pos as f32;
+        let l = self.resample_prev.0 + (self.resample_curr.0 - self.resample_prev.0) * frac;
+        let r = self.resample_prev.1 + (self.resample_curr.1 - self.resample_prev.1) * frac;
+        self.resample_pos += self.resample_step;
+
+        (l, r)
+    }
+
+    // Full-precision filtered stereo frame, before the lossy 16-bit
+    // scale/clamp. Wider export formats keep this precision.
+    fn generate_frame(&mut self) -> (f32, f32) {
+        let (l, r) = self.next_resampled();
+
+        // Run the configurable output filters independently per L/R channel.
+        let mut channels = [l, r];
+        if self.filter.high_pass {
+            let a = self.filter.hp_coeff();
+            for (i, sample) in channels.iter_mut().enumerate() {
+                *sample = self.high_pass[i].process(a, *sample);
+            }
+        }
+        if self.filter.low_pass {
+            let b = self.filter.lp_coeff();
+            for (i, sample) in channels.iter_mut().enumerate() {
+                *sample = self.low_pass[i].process(b, *sample);
+            }
+        }
+
+        (channels[0], channels[1])
+    }
+
+    fn generate_sample(&mut self) -> (i16, i16) {
+        let (l, r) = self.generate_frame();
+
         // Shift right by 5 bits to reduce amplitude (as in the example)
         // and convert to 16-bit samples
-        let left = (output[0] >> 5).clamp(-32768, 32767) as i16;
-        let right = (output[1] >> 5).clamp(-32768, 32767) as i16;
-        
+        let left = ((l as i32) >> 5).clamp(-32768, 32767) as i16;
+        let right = ((r as i32) >> 5).clamp(-32768, 32767) as i16;
+
         (left, right)
     }
-    
+
+    // Full-precision frame normalized to [-1, 1], matching the level of the
+    // legacy `>>5` 16-bit path (divide by 32, then by the 16-bit full scale).
+    fn generate_frame_normalized(&mut self) -> (f32, f32) {
+        const SCALE: f32 = 32.0 * 32768.0;
+        let (l, r) = self.generate_frame();
+        ((l / SCALE).clamp(-1.0, 1.0), (r / SCALE).clamp(-1.0, 1.0))
+    }
+
     fn generate_samples(&mut self, count: usize) -> Vec<(i16, i16)> {
         let mut samples = Vec::with_capacity(count);
         for _ in 0..count {
@@ -129,75 +265,263 @@ impl Ym2151 {
         }
         samples
     }
+
+    // Render `count` full-precision, normalized frames for multi-format export.
+    fn generate_frames(&mut self, count: usize) -> Vec<(f32, f32)> {
+        let mut frames = Vec::with_capacity(count);
+        for _ in 0..count {
+            frames.push(self.generate_frame_normalized());
+        }
+        frames
+    }
     
     fn write_with_delay(&mut self, address: u8, data: u8, samples: &mut Vec<(i16, i16)>) {
         self.write_register(address, data);
-        // Consume 10ms worth of samples after register write
-        let samples_for_10ms = ((SAMPLE_RATE as f64) * 0.01) as usize;
+        // Consume 10ms worth of samples after register write, at the configured
+        // output rate.
+        let samples_for_10ms = ((self.sample_rate as f64) * 0.01) as usize;
         let new_samples = self.generate_samples(samples_for_10ms);
         samples.extend(new_samples);
     }
 }
 
-fn setup_440hz_tone(ym: &mut Ym2151, samples: &mut Vec<(i16, i16)>) {
-    // Reset all channels first
-    for ch in 0..8 {
-        ym.write_register(0x08, ch);
-    }
-    
-    let channel = 0u8;
-    
-    // RL_FB_CONNECT: RL=11 (both L/R), FB=0, CON=7
-    // 0xC7 = 11000111 binary
-    ym.write_with_delay(0x20 + channel, 0xC7, samples);
-    
-    // KC (Key Code) for A4 (440Hz)
-    // 0x4A gives approximately 440Hz
-    ym.write_with_delay(0x28 + channel, 0x4A, samples);
-    
-    // KF (Key Fraction)
-    ym.write_with_delay(0x30 + channel, 0x00, samples);
-    
-    // PMS/AMS
-    ym.write_with_delay(0x38 + channel, 0x00, samples);
-    
+// The ordered register program for the 440Hz reference tone on `channel`,
+// ending with the key-on write. Shared by the batch WAV demo and the live
+// streaming demo so both drive the chip identically.
+fn tone_program(channel: u8) -> Vec<(u8, u8)> {
+    let mut program = vec![
+        // RL_FB_CONNECT: RL=11 (both L/R), FB=0, CON=7 (0xC7 = 11000111)
+        (0x20 + channel, 0xC7),
+        // KC (Key Code) for A4 (440Hz); 0x4A gives approximately 440Hz
+        (0x28 + channel, 0x4A),
+        // KF (Key Fraction)
+        (0x30 + channel, 0x00),
+        // PMS/AMS
+        (0x38 + channel, 0x00),
+    ];
+
     // Configure all 4 operators
     for op in 0..4 {
         let slot = channel + (op * 8);
-        
         // DT1/MUL: MUL=1 for fundamental frequency
-        ym.write_with_delay(0x40 + slot, 0x01, samples);
-        
-        // TL (Total Level): 0 = max volume for operator 0 (carrier), silent for others
-        if op == 0 {
-            ym.write_with_delay(0x60 + slot, 0x00, samples); // Max volume for carrier
-        } else {
-            ym.write_with_delay(0x60 + slot, 0x7F, samples); // Silent for modulators
-        }
-        
+        program.push((0x40 + slot, 0x01));
+        // TL (Total Level): 0 = max volume for operator 0, the other three muted
+        // (with CON=7 all four are carriers)
+        program.push((0x60 + slot, if op == 0 { 0x00 } else { 0x7F }));
         // KS/AR: AR=31 for instant attack
-        ym.write_with_delay(0x80 + slot, 0x1F, samples);
-        
+        program.push((0x80 + slot, 0x1F));
         // AMS/D1R: D1R=5
-        ym.write_with_delay(0xA0 + slot, 0x05, samples);
-        
+        program.push((0xA0 + slot, 0x05));
         // DT2/D2R: D2R=5
-        ym.write_with_delay(0xC0 + slot, 0x05, samples);
-        
+        program.push((0xC0 + slot, 0x05));
         // D1L/RR: D1L=15, RR=7
-        ym.write_with_delay(0xE0 + slot, 0xF7, samples);
+        program.push((0xE0 + slot, 0xF7));
     }
-    
+
     // Key on: 0x78 | channel
-    ym.write_with_delay(0x08, 0x78 | channel, samples);
+    program.push((0x08, 0x78 | channel));
+    program
+}
+
+fn setup_440hz_tone(ym: &mut Ym2151, samples: &mut Vec<(i16, i16)>) {
+    // Reset all channels first
+    for ch in 0..8 {
+        ym.write_register(0x08, ch);
+    }
+
+    for (address, data) in tone_program(0) {
+        ym.write_with_delay(address, data, samples);
+    }
+}
+
+fn render_vgm_file(path: &str, spec: OutputSpec, low_pass: bool) {
+    println!("Rendering VGM file {} using Nuked-OPM...", path);
+
+    let bytes = std::fs::read(path).expect("Failed to read VGM file");
+    let song = vgm::Vgm::parse(&bytes).expect("Failed to parse VGM file");
+    println!(
+        "Parsed VGM: chip clock {} Hz, {} samples",
+        song.chip_clock, song.total_samples
+    );
+
+    // Clock the emulation at the file's declared YM2151 rate so pitch is
+    // correct; fall back to the default clock when the header carries none.
+    let chip_clock = if song.chip_clock != 0 {
+        song.chip_clock
+    } else {
+        CHIP_CLOCK
+    };
+    let mut ym = Ym2151::with_rates(SAMPLE_RATE, chip_clock);
+    if low_pass {
+        enable_low_pass(&mut ym);
+    }
+    let frames = song.render(&mut ym);
+
+    let output_path = format!("output_vgm.{}", spec.extension());
+    output::write(&output_path, &frames, SAMPLE_RATE, spec).expect("Failed to write output");
+
+    println!("Successfully rendered {} samples to {}", frames.len(), output_path);
+}
+
+// A simple demo instrument: algorithm 7 (all four operators are carriers) with
+// only the first operator audible and the other three muted, routed to both
+// outputs. Mirrors the levels of `setup_440hz_tone`
+// but authored through the high-level `voice` API.
+fn demo_patch() -> voice::Patch {
+    let carrier = voice::Operator {
+        dt1: 0,
+        mul: 1,
+        level: 0.0,
+        ar: 31,
+        d1r: 5,
+        d2r: 5,
+        d1l: 15,
+        rr: 7,
+    };
+    let muted = voice::Operator {
+        level: -96.0,
+        ..carrier
+    };
+    voice::Patch {
+        operators: [carrier, muted, muted, muted],
+        feedback: 0,
+        connect: 7,
+        left: true,
+        right: true,
+    }
+}
+
+// Enable the treble low-pass (off by default) on `ym`, recomputing its
+// coefficients for the current output rate via `set_filter`.
+fn enable_low_pass(ym: &mut Ym2151) {
+    let mut filter = FilterConfig::new();
+    filter.low_pass = true;
+    ym.set_filter(filter);
+}
+
+fn render_voice_demo(spec: OutputSpec, low_pass: bool) {
+    println!("Playing a C-major chord through the high-level voice API...");
+
+    let mut ym = Ym2151::new();
+    if low_pass {
+        enable_low_pass(&mut ym);
+    }
+    let patch = demo_patch();
+    {
+        let mut synth = voice::Synth::new(&mut ym, &patch);
+        for note in ["C4", "E4", "G4"] {
+            synth.note_on(note);
+        }
+    }
+
+    let frames = ym.generate_frames(SAMPLE_RATE as usize * 2);
+    let output_path = format!("output_voice.{}", spec.extension());
+    output::write(&output_path, &frames, SAMPLE_RATE, spec).expect("Failed to write output");
+    println!("Successfully rendered {} samples to {}", frames.len(), output_path);
+}
+
+fn render_midi_demo(spec: OutputSpec, low_pass: bool) {
+    println!("Playing a MIDI note sequence through the voice-stealing allocator...");
+
+    let mut ym = Ym2151::new();
+    if low_pass {
+        enable_low_pass(&mut ym);
+    }
+    {
+        let mut synth = midi::MidiSynth::new(&mut ym, demo_patch());
+        // Ten overlapping notes on eight channels exercises allocation and then
+        // voice stealing; the final note-off releases one of them.
+        for note in 60u8..=69 {
+            synth.note_on(note, 100);
+        }
+        synth.note_off(69);
+    }
+
+    let frames = ym.generate_frames(SAMPLE_RATE as usize * 2);
+    let output_path = format!("output_midi.{}", spec.extension());
+    output::write(&output_path, &frames, SAMPLE_RATE, spec).expect("Failed to write output");
+    println!("Successfully rendered {} samples to {}", frames.len(), output_path);
+}
+
+fn play_live() {
+    println!("Streaming the 440Hz tone live to the default audio device for 3 seconds...");
+
+    let ym = Ym2151::new();
+    let player = match audio::AudioPlayer::start(ym, None) {
+        Ok(player) => player,
+        Err(e) => {
+            eprintln!("could not start audio playback: {}", e);
+            return;
+        }
+    };
+
+    // Reset the channels, then feed the reference tone straight to the worker.
+    for ch in 0..8 {
+        player.write_register(0x08, ch);
+    }
+    for (address, data) in tone_program(0) {
+        player.write_register(address, data);
+    }
+
+    std::thread::sleep(std::time::Duration::from_secs(3));
 }
 
 fn main() {
+    // An optional trailing argument names the output format (wav24, wav32f,
+    // mono, raw); the default is 16-bit stereo WAV.
+    let args: Vec<String> = std::env::args().collect();
+
+    // Mode selectors and the filter toggle are ordinary non-`.vgm` arguments,
+    // so exclude them when looking for the format name or `... voice wav24`
+    // would match `voice` and silently fall back to 16-bit stereo.
+    const KEYWORDS: [&str; 4] = ["voice", "midi", "play", "lowpass"];
+    let spec = args
+        .iter()
+        .skip(1)
+        .find(|a| !a.ends_with(".vgm") && !KEYWORDS.contains(&a.as_str()))
+        .map(|name| OutputSpec::named(name))
+        .unwrap_or_else(OutputSpec::wav16);
+
+    // The optional `lowpass` keyword enables the treble low-pass filter, which
+    // is off by default, on whichever render path runs.
+    let low_pass = args.iter().any(|a| a == "lowpass");
+
+    // The `play` keyword streams live to the default audio device instead of
+    // writing a file.
+    if args.iter().any(|a| a == "play") {
+        play_live();
+        return;
+    }
+
+    // The `voice` keyword renders a short chord through the high-level API.
+    if args.iter().any(|a| a == "voice") {
+        render_voice_demo(spec, low_pass);
+        return;
+    }
+
+    // The `midi` keyword drives the MIDI sound module with a note sequence.
+    if args.iter().any(|a| a == "midi") {
+        render_midi_demo(spec, low_pass);
+        return;
+    }
+
+    // A VGM file path on the command line renders that song; otherwise fall back
+    // to the original 440Hz demo tone.
+    if let Some(path) = args.get(1) {
+        if path.ends_with(".vgm") {
+            render_vgm_file(path, spec, low_pass);
+            return;
+        }
+    }
+
     println!("Generating 440Hz 3-second WAV file using Nuked-OPM...");
-    
+
     let mut ym = Ym2151::new();
+    if low_pass {
+        enable_low_pass(&mut ym);
+    }
     let mut samples = Vec::new();
-    
+
     // Setup the tone
     setup_440hz_tone(&mut ym, &mut samples);
     
@@ -229,25 +553,15 @@ fn main() {
         println!("Max amplitude - Left: {}, Right: {}", max_left, max_right);
     }
     
-    // Write to WAV file
-    let spec = hound::WavSpec {
-        channels: 2,
-        sample_rate: SAMPLE_RATE,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    
-    let output_path = "output_440hz.wav";
-    let mut writer = hound::WavWriter::create(output_path, spec)
-        .expect("Failed to create WAV file");
-    
-    for (left, right) in samples {
-        writer.write_sample(left).expect("Failed to write sample");
-        writer.write_sample(right).expect("Failed to write sample");
-    }
-    
-    writer.finalize().expect("Failed to finalize WAV file");
-    
+    // Write to the selected output format. The demo setup collects 16-bit
+    // samples, so normalize them back into the shared export path.
+    let frames: Vec<(f32, f32)> = samples
+        .iter()
+        .map(|&(l, r)| (l as f32 / 32768.0, r as f32 / 32768.0))
+        .collect();
+    let output_path = format!("output_440hz.{}", spec.extension());
+    output::write(&output_path, &frames, SAMPLE_RATE, spec).expect("Failed to write output");
+
     println!("Successfully generated {} with {} samples", output_path, total_samples);
     println!();
     println!("To play the file on Windows: start {}", output_path);
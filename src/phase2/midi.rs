@@ -0,0 +1,180 @@
+// MIDI-driven real-time synthesis.
+//
+// Accepts MIDI note-on / note-off events and plays them across the eight
+// YM2151 channels, with a voice-stealing allocator and per-note velocity mapped
+// onto the carrier total level. This lets the crate act as an FM sound module
+// rather than a batch WAV generator.
+
+use crate::voice::{gain_to_tl, note_to_kc, Patch, NUM_CHANNELS};
+use crate::Ym2151;
+
+// Which operators are carriers (produce audible output) for each of the eight
+// connection algorithms. Velocity is applied to exactly these slots.
+const CARRIERS: [[bool; 4]; 8] = [
+    [false, false, false, true], // CON 0
+    [false, false, false, true], // CON 1
+    [false, false, false, true], // CON 2
+    [false, false, false, true], // CON 3
+    [false, false, true, true],  // CON 4
+    [false, true, true, true],   // CON 5
+    [false, true, true, true],   // CON 6
+    [true, true, true, true],    // CON 7
+];
+
+// Convert a MIDI note number to the YM2151 key-code (KC) and key-fraction (KF)
+// registers. MIDI note 69 is A4 (A440) and note 60 is C4; the octave and
+// in-octave index follow the standard MIDI layout (0 = C). Any cents remainder
+// is placed in the 6-bit KF field (bits 7-2 of the register byte); integer MIDI
+// notes land exactly on a semitone, so their KF is zero.
+pub fn midi_note_to_kc_kf(note: u8) -> (u8, u8) {
+    // MIDI note split into octave (note 60 = C4) plus in-octave index (0 = C).
+    let octave = note as i32 / 12 - 1;
+    let index = (note % 12) as usize;
+    let kc = note_to_kc(octave, index);
+
+    // Integer semitone -> no fractional pitch. The 6-bit fraction would occupy
+    // bits 7-2 of the KF register if present.
+    let fraction: u8 = 0;
+    let kf = fraction << 2;
+
+    (kc, kf)
+}
+
+// Map a 7-bit MIDI velocity onto a carrier TL value (0 loudest, 127 silent).
+fn velocity_to_tl(velocity: u8) -> u8 {
+    if velocity == 0 {
+        return 127;
+    }
+    gain_to_tl(velocity as f32 / 127.0)
+}
+
+// Tracks what each channel is currently sounding.
+#[derive(Clone, Copy)]
+struct Voice {
+    note: Option<u8>,
+    // Allocation timestamp, used to steal the oldest voice when all are busy.
+    age: u64,
+}
+
+// A MIDI sound module driving a single `Patch` across the eight channels.
+pub struct MidiSynth<'a> {
+    ym: &'a mut Ym2151,
+    patch: Patch,
+    voices: [Voice; NUM_CHANNELS as usize],
+    clock: u64,
+}
+
+impl<'a> MidiSynth<'a> {
+    // Create a module with `patch` loaded onto every channel.
+    pub fn new(ym: &'a mut Ym2151, patch: Patch) -> Self {
+        for ch in 0..NUM_CHANNELS {
+            ym.write_register(0x08, ch); // key off
+            patch.apply(ym, ch);
+        }
+        Self {
+            ym,
+            patch,
+            voices: [Voice { note: None, age: 0 }; NUM_CHANNELS as usize],
+            clock: 0,
+        }
+    }
+
+    // Handle a MIDI note-on, allocating a channel (stealing the oldest voice if
+    // none are free) and returning the channel used. A zero velocity is a
+    // note-off: it returns the released channel, or the oldest voice's channel
+    // when no voice holds `note`.
+    pub fn note_on(&mut self, note: u8, velocity: u8) -> u8 {
+        if velocity == 0 {
+            // Running-status note-off.
+            if let Some(ch) = self.find_channel(note) {
+                self.key_off(ch);
+                return ch;
+            }
+            return self.steal();
+        }
+
+        let channel = self.allocate();
+        let (kc, kf) = midi_note_to_kc_kf(note);
+        self.ym.write_register(0x28 + channel, kc);
+        self.ym.write_register(0x30 + channel, kf);
+
+        let tl = velocity_to_tl(velocity);
+        for (op, carrier) in CARRIERS[(self.patch.connect & 7) as usize].iter().enumerate() {
+            if *carrier {
+                let slot = channel + (op as u8 * 8);
+                self.ym.write_register(0x60 + slot, tl);
+            }
+        }
+
+        self.ym.write_register(0x08, 0x78 | channel);
+        self.clock += 1;
+        self.voices[channel as usize] = Voice {
+            note: Some(note),
+            age: self.clock,
+        };
+        channel
+    }
+
+    // Handle a MIDI note-off for `note`.
+    pub fn note_off(&mut self, note: u8) {
+        if let Some(channel) = self.find_channel(note) {
+            self.key_off(channel);
+        }
+    }
+
+    fn key_off(&mut self, channel: u8) {
+        self.ym.write_register(0x08, channel);
+        self.voices[channel as usize].note = None;
+    }
+
+    fn find_channel(&self, note: u8) -> Option<u8> {
+        self.voices
+            .iter()
+            .position(|v| v.note == Some(note))
+            .map(|ch| ch as u8)
+    }
+
+    // Pick a free channel, or the oldest sounding one if all are busy.
+    fn allocate(&mut self) -> u8 {
+        if let Some(ch) = self.voices.iter().position(|v| v.note.is_none()) {
+            return ch as u8;
+        }
+        self.steal()
+    }
+
+    // Return the channel holding the oldest voice.
+    fn steal(&self) -> u8 {
+        self.voices
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, v)| v.age)
+            .map(|(ch, _)| ch as u8)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a440_maps_to_reference_kc() {
+        // MIDI note 69 is A4 (A440); its KC matches the demo's 0x4A and the
+        // fraction is zero for an integer semitone.
+        assert_eq!(midi_note_to_kc_kf(69), (0x4A, 0));
+    }
+
+    #[test]
+    fn octaves_step_the_high_nibble() {
+        // C4 and C5 differ by one key-code octave (0x10) apart.
+        let (c4, _) = midi_note_to_kc_kf(60);
+        let (c5, _) = midi_note_to_kc_kf(72);
+        assert_eq!(c5 - c4, 0x10);
+    }
+
+    #[test]
+    fn zero_velocity_is_fully_attenuated() {
+        assert_eq!(velocity_to_tl(0), 127);
+        assert_eq!(velocity_to_tl(127), 0);
+    }
+}
@@ -0,0 +1,205 @@
+// Multi-format / bit-depth output stage.
+//
+// Extends the fixed 16-bit stereo WAV writer to 24-bit and 32-bit-float WAV,
+// mono downmix and headerless raw PCM, all selected through an `OutputSpec`.
+// Because `generate_frame` keeps the chip's full precision before the lossy
+// `>>5` clamp, the wider formats carry that precision through instead of
+// throwing bits away.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// Sample bit depth / encoding of the exported audio.
+#[derive(Clone, Copy)]
+pub enum Depth {
+    Int16,
+    Int24,
+    Float32,
+}
+
+// Container the samples are written into.
+#[derive(Clone, Copy)]
+pub enum Container {
+    Wav,
+    Raw,
+}
+
+// How to render a normalized sample stream to disk.
+#[derive(Clone, Copy)]
+pub struct OutputSpec {
+    pub depth: Depth,
+    pub container: Container,
+    pub mono: bool,
+}
+
+impl OutputSpec {
+    // The original behaviour: 16-bit stereo WAV.
+    pub fn wav16() -> Self {
+        Self {
+            depth: Depth::Int16,
+            container: Container::Wav,
+            mono: false,
+        }
+    }
+
+    // Resolve a short format name, falling back to 16-bit stereo WAV.
+    pub fn named(name: &str) -> Self {
+        match name {
+            "wav24" => Self {
+                depth: Depth::Int24,
+                container: Container::Wav,
+                mono: false,
+            },
+            "wav32f" | "float" => Self {
+                depth: Depth::Float32,
+                container: Container::Wav,
+                mono: false,
+            },
+            "mono" => Self {
+                depth: Depth::Int16,
+                container: Container::Wav,
+                mono: true,
+            },
+            "raw" => Self {
+                depth: Depth::Int16,
+                container: Container::Raw,
+                mono: false,
+            },
+            _ => Self::wav16(),
+        }
+    }
+
+    // File extension matching this spec's container.
+    pub fn extension(&self) -> &'static str {
+        match self.container {
+            Container::Wav => "wav",
+            Container::Raw => "raw",
+        }
+    }
+}
+
+fn to_i16(x: f32) -> i16 {
+    (x * 32767.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+fn to_i24(x: f32) -> i32 {
+    (x * 8_388_607.0).round().clamp(-8_388_608.0, 8_388_607.0) as i32
+}
+
+// Write normalized stereo frames (each channel in [-1, 1]) to `path` using
+// `spec`.
+pub fn write(
+    path: &str,
+    frames: &[(f32, f32)],
+    sample_rate: u32,
+    spec: OutputSpec,
+) -> Result<(), String> {
+    match spec.container {
+        Container::Wav => write_wav(path, frames, sample_rate, spec),
+        Container::Raw => write_raw(path, frames, spec),
+    }
+}
+
+fn write_wav(
+    path: &str,
+    frames: &[(f32, f32)],
+    sample_rate: u32,
+    spec: OutputSpec,
+) -> Result<(), String> {
+    let (bits_per_sample, sample_format) = match spec.depth {
+        Depth::Int16 => (16, hound::SampleFormat::Int),
+        Depth::Int24 => (24, hound::SampleFormat::Int),
+        Depth::Float32 => (32, hound::SampleFormat::Float),
+    };
+
+    let wav_spec = hound::WavSpec {
+        channels: if spec.mono { 1 } else { 2 },
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    };
+
+    let mut writer = hound::WavWriter::create(path, wav_spec).map_err(|e| e.to_string())?;
+    for &(l, r) in frames {
+        if spec.mono {
+            write_wav_sample(&mut writer, spec.depth, (l + r) * 0.5)?;
+        } else {
+            write_wav_sample(&mut writer, spec.depth, l)?;
+            write_wav_sample(&mut writer, spec.depth, r)?;
+        }
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+fn write_wav_sample(
+    writer: &mut hound::WavWriter<BufWriter<File>>,
+    depth: Depth,
+    x: f32,
+) -> Result<(), String> {
+    match depth {
+        Depth::Int16 => writer.write_sample(to_i16(x)),
+        Depth::Int24 => writer.write_sample(to_i24(x)),
+        Depth::Float32 => writer.write_sample(x),
+    }
+    .map_err(|e| e.to_string())
+}
+
+fn write_raw(path: &str, frames: &[(f32, f32)], spec: OutputSpec) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    for &(l, r) in frames {
+        if spec.mono {
+            write_raw_sample(&mut writer, spec.depth, (l + r) * 0.5)?;
+        } else {
+            write_raw_sample(&mut writer, spec.depth, l)?;
+            write_raw_sample(&mut writer, spec.depth, r)?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn write_raw_sample(
+    writer: &mut BufWriter<File>,
+    depth: Depth,
+    x: f32,
+) -> Result<(), String> {
+    match depth {
+        Depth::Int16 => writer.write_all(&to_i16(x).to_le_bytes()),
+        // 24-bit little-endian: the low three bytes of the signed value.
+        Depth::Int24 => writer.write_all(&to_i24(x).to_le_bytes()[0..3]),
+        Depth::Float32 => writer.write_all(&x.to_le_bytes()),
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_i16_maps_full_scale_and_clamps() {
+        assert_eq!(to_i16(0.0), 0);
+        assert_eq!(to_i16(1.0), 32767);
+        assert_eq!(to_i16(-1.0), -32767);
+        // Out-of-range input clamps rather than wrapping.
+        assert_eq!(to_i16(2.0), 32767);
+        assert_eq!(to_i16(-2.0), -32768);
+    }
+
+    #[test]
+    fn to_i24_maps_full_scale_and_clamps() {
+        assert_eq!(to_i24(0.0), 0);
+        assert_eq!(to_i24(1.0), 8_388_607);
+        assert_eq!(to_i24(-1.0), -8_388_607);
+        assert_eq!(to_i24(2.0), 8_388_607);
+        assert_eq!(to_i24(-2.0), -8_388_608);
+    }
+
+    #[test]
+    fn named_formats_resolve() {
+        assert!(matches!(OutputSpec::named("raw").container, Container::Raw));
+        assert!(OutputSpec::named("mono").mono);
+        assert!(matches!(OutputSpec::named("wav24").depth, Depth::Int24));
+        // Unknown names fall back to 16-bit stereo WAV.
+        assert!(matches!(OutputSpec::named("???").depth, Depth::Int16));
+    }
+}
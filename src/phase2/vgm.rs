@@ -0,0 +1,223 @@
+// VGM file player for the YM2151 path.
+//
+// Parses a standard VGM file and drives its command stream through the existing
+// `Ym2151` FFI wrapper, so the crate renders real songs instead of the single
+// hardcoded 440Hz tone.
+
+use crate::Ym2151;
+
+// Header field offsets (little-endian u32 unless noted).
+const OFFSET_MAGIC: usize = 0x00;
+const OFFSET_TOTAL_SAMPLES: usize = 0x18;
+const OFFSET_YM2151_CLOCK: usize = 0x30;
+const OFFSET_DATA_OFFSET: usize = 0x34;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let b = self.data.get(self.pos).copied();
+        self.pos += 1;
+        b
+    }
+
+    // Little-endian u32, or None if the stream ends mid-value.
+    fn u32(&mut self) -> Option<u32> {
+        let b0 = self.byte()? as u32;
+        let b1 = self.byte()? as u32;
+        let b2 = self.byte()? as u32;
+        let b3 = self.byte()? as u32;
+        Some(b0 | (b1 << 8) | (b2 << 16) | (b3 << 24))
+    }
+
+    // Advance past `n` operand bytes belonging to a command we don't interpret.
+    fn skip(&mut self, n: usize) {
+        self.pos = self.pos.saturating_add(n);
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+// A parsed VGM file ready to be rendered through a `Ym2151`.
+pub struct Vgm {
+    // The chip clock declared in the header (0 if the file carries no YM2151).
+    pub chip_clock: u32,
+    // Total number of 44100 Hz samples the song is expected to produce.
+    pub total_samples: u32,
+    data: Vec<u8>,
+    data_offset: usize,
+}
+
+impl Vgm {
+    // Parse a VGM file from raw bytes, validating the magic and header fields.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 0x100 {
+            return Err("VGM file is smaller than the 0x100-byte header".to_string());
+        }
+        if &bytes[OFFSET_MAGIC..OFFSET_MAGIC + 4] != b"Vgm " {
+            return Err("missing \"Vgm \" magic".to_string());
+        }
+
+        let total_samples = read_u32(bytes, OFFSET_TOTAL_SAMPLES);
+        let chip_clock = read_u32(bytes, OFFSET_YM2151_CLOCK);
+
+        // The data offset is relative to its own field; older (pre-1.50) files
+        // leave it zero and start the stream at 0x40.
+        let relative = read_u32(bytes, OFFSET_DATA_OFFSET) as usize;
+        let data_offset = if relative == 0 {
+            0x40
+        } else {
+            OFFSET_DATA_OFFSET + relative
+        };
+        if data_offset >= bytes.len() {
+            return Err("VGM data offset points past end of file".to_string());
+        }
+
+        Ok(Self {
+            chip_clock,
+            total_samples,
+            data: bytes.to_vec(),
+            data_offset,
+        })
+    }
+
+    // Render the command stream to a full-precision stereo buffer. Register
+    // writes go straight to `write_register`; every wait clocks the chip for the
+    // requested number of samples. VGM wait counts are absolute 1/44100 s
+    // durations and must not be rescaled by the chip clock — only the chip's
+    // pitch depends on the clock, which the caller sets via `Ym2151::with_rates`.
+    //
+    // Commands addressing chips we don't emulate are skipped by their standard
+    // VGM operand lengths (rather than stopping the stream) so the YM2151 part
+    // of a multi-chip file still plays to the end.
+    pub fn render(&self, ym: &mut Ym2151) -> Vec<(f32, f32)> {
+        let mut samples = Vec::new();
+        let mut wait = |ym: &mut Ym2151, n: u32, out: &mut Vec<(f32, f32)>| {
+            out.extend(ym.generate_frames(n as usize));
+        };
+
+        let mut reader = Reader::new(&self.data, self.data_offset);
+        while let Some(opcode) = reader.byte() {
+            match opcode {
+                // YM2151 register write: 0x54 aa dd
+                0x54 => {
+                    let address = match reader.byte() {
+                        Some(a) => a,
+                        None => break,
+                    };
+                    let data = match reader.byte() {
+                        Some(d) => d,
+                        None => break,
+                    };
+                    ym.write_register(address, data);
+                }
+                // Wait N samples: 0x61 nn nn (little-endian u16)
+                0x61 => {
+                    let lo = reader.byte().unwrap_or(0) as u32;
+                    let hi = reader.byte().unwrap_or(0) as u32;
+                    wait(ym, lo | (hi << 8), &mut samples);
+                }
+                // Wait one 60Hz frame (735 samples)
+                0x62 => wait(ym, 735, &mut samples),
+                // Wait one 50Hz frame (882 samples)
+                0x63 => wait(ym, 882, &mut samples),
+                // End of stream
+                0x66 => break,
+                // Data block: 0x67 0x66 tt ss(u32) followed by `ss` payload bytes.
+                0x67 => {
+                    reader.byte(); // compatibility 0x66
+                    reader.byte(); // block type
+                    let size = reader.u32().unwrap_or(0);
+                    reader.skip(size as usize);
+                }
+                // PCM RAM write: 0x68 0x66 plus 11 operand bytes.
+                0x68 => reader.skip(12),
+                // Short wait of (low-nibble + 1) samples
+                0x70..=0x7F => wait(ym, (opcode & 0x0F) as u32 + 1, &mut samples),
+                // YM2612 DAC-and-wait family: no operand bytes.
+                0x80..=0x8F => {}
+                // DAC stream control block (0x90-0x95), by operand length.
+                0x90 | 0x91 | 0x95 => reader.skip(4),
+                0x92 => reader.skip(5),
+                0x93 => reader.skip(10),
+                0x94 => reader.skip(1),
+                // Foreign single-operand chip writes (PSG, GG stereo, reserved).
+                0x30..=0x3F | 0x4F | 0x50 => reader.skip(1),
+                // Foreign two-operand chip writes.
+                0x40..=0x4E | 0x51..=0x5F | 0xA0..=0xBF => reader.skip(2),
+                // Foreign three- and four-operand chip writes.
+                0xC0..=0xDF => reader.skip(3),
+                0xE0..=0xFF => reader.skip(4),
+                // Truly unknown opcode: stop rather than desynchronise the stream.
+                _ => break,
+            }
+        }
+
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Build a minimal, valid header with the given fields filled in.
+    fn header(total_samples: u32, chip_clock: u32, data_relative: u32) -> Vec<u8> {
+        let mut bytes = vec![0u8; 0x140];
+        bytes[OFFSET_MAGIC..OFFSET_MAGIC + 4].copy_from_slice(b"Vgm ");
+        bytes[OFFSET_TOTAL_SAMPLES..OFFSET_TOTAL_SAMPLES + 4]
+            .copy_from_slice(&total_samples.to_le_bytes());
+        bytes[OFFSET_YM2151_CLOCK..OFFSET_YM2151_CLOCK + 4]
+            .copy_from_slice(&chip_clock.to_le_bytes());
+        bytes[OFFSET_DATA_OFFSET..OFFSET_DATA_OFFSET + 4]
+            .copy_from_slice(&data_relative.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn parses_header_fields() {
+        let song = Vgm::parse(&header(44100, 3_579_545, 0x20)).unwrap();
+        assert_eq!(song.total_samples, 44100);
+        assert_eq!(song.chip_clock, 3_579_545);
+        assert_eq!(song.data_offset, OFFSET_DATA_OFFSET + 0x20);
+    }
+
+    #[test]
+    fn zero_data_offset_defaults_to_0x40() {
+        let song = Vgm::parse(&header(0, 0, 0)).unwrap();
+        assert_eq!(song.data_offset, 0x40);
+    }
+
+    #[test]
+    fn reader_reads_u32_and_skips() {
+        let mut reader = Reader::new(&[0x78, 0x56, 0x34, 0x12, 0xAA, 0xBB], 0);
+        assert_eq!(reader.u32(), Some(0x1234_5678));
+        reader.skip(1);
+        assert_eq!(reader.byte(), Some(0xBB));
+        // Skipping past the end leaves subsequent reads empty, not panicking.
+        reader.skip(100);
+        assert_eq!(reader.byte(), None);
+    }
+
+    #[test]
+    fn rejects_short_and_unmagicked_files() {
+        assert!(Vgm::parse(&[0u8; 0x10]).is_err());
+        let mut bad = header(0, 0, 0);
+        bad[0] = b'X';
+        assert!(Vgm::parse(&bad).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+// High-level FM voice/patch API.
+//
+// Lets a user define an instrument once in musically meaningful units and then
+// trigger notes by name, instead of hand-writing `0x40+slot` / `0x60+slot`
+// register pokes the way `setup_440hz_tone` does.
+
+use crate::Ym2151;
+
+// The YM2151 has eight FM channels.
+pub const NUM_CHANNELS: u8 = 8;
+
+// Each TL step attenuates the operator output by 0.75 dB.
+const TL_STEP_DB: f32 = 0.75;
+
+// Convert a level in decibels to a linear gain (gain = 10^(dB/20)).
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+// Map a linear gain in (0, 1] onto the chip's 0-127 TL scale, where 0 is the
+// loudest and 127 is fully attenuated.
+pub(crate) fn gain_to_tl(gain: f32) -> u8 {
+    if gain <= 0.0 {
+        return 127;
+    }
+    let atten_db = -20.0 * gain.log10();
+    (atten_db / TL_STEP_DB).round().clamp(0.0, 127.0) as u8
+}
+
+// A single FM operator, authored in musical units.
+pub struct Operator {
+    pub dt1: u8,  // detune 1 (0-7)
+    pub mul: u8,  // frequency multiplier (0-15)
+    pub level: f32, // output level in dB relative to full scale (0.0 = loudest, negative = quieter)
+    pub ar: u8,   // attack rate (0-31)
+    pub d1r: u8,  // first decay rate (0-31)
+    pub d2r: u8,  // second decay rate (0-31)
+    pub d1l: u8,  // first decay level (0-15)
+    pub rr: u8,   // release rate (0-15)
+}
+
+// An instrument: four operators plus per-channel routing.
+pub struct Patch {
+    pub operators: [Operator; 4],
+    pub feedback: u8,  // self-feedback on operator 1 (0-7)
+    pub connect: u8,   // algorithm / operator connection (0-7)
+    pub left: bool,    // route to the left output
+    pub right: bool,   // route to the right output
+}
+
+impl Patch {
+    // Write this patch's operator and channel settings to `channel`. Does not
+    // touch the key-on state or the note pitch.
+    pub(crate) fn apply(&self, ym: &mut Ym2151, channel: u8) {
+        let rl = ((self.right as u8) << 7) | ((self.left as u8) << 6);
+        ym.write_register(0x20 + channel, rl | (self.feedback << 3) | self.connect);
+
+        for (op, operator) in self.operators.iter().enumerate() {
+            let slot = channel + (op as u8 * 8);
+            ym.write_register(0x40 + slot, (operator.dt1 << 4) | operator.mul);
+            ym.write_register(0x60 + slot, gain_to_tl(db_to_gain(operator.level)));
+            ym.write_register(0x80 + slot, operator.ar);
+            ym.write_register(0xA0 + slot, operator.d1r);
+            ym.write_register(0xC0 + slot, operator.d2r);
+            ym.write_register(0xE0 + slot, (operator.d1l << 4) | operator.rr);
+        }
+    }
+}
+
+// OPM note codes for the 12 semitones C..B. Codes 3, 7, 11, 15 are unused by
+// the chip, so only these twelve appear. C sits at the top of the previous
+// key-code octave, which the converter below accounts for.
+const NOTE_CODES: [u8; 12] = [14, 0, 1, 2, 4, 5, 6, 8, 9, 10, 12, 13];
+
+// Convert a musical octave and in-octave semitone index (0 = C .. 11 = B) to a
+// KC register value. Reproduces the original demo's 0x4A for A4.
+pub(crate) fn note_to_kc(octave: i32, index: usize) -> u8 {
+    if index == 0 {
+        // C belongs to the previous key-code octave.
+        let oct = (octave - 1).clamp(0, 7) as u8;
+        (oct << 4) | NOTE_CODES[0]
+    } else {
+        let oct = octave.clamp(0, 7) as u8;
+        (oct << 4) | NOTE_CODES[index]
+    }
+}
+
+// Parse a scientific note name such as "A4", "C#5" or "Eb3" into a KC value.
+fn parse_note(name: &str) -> Option<u8> {
+    let mut chars = name.chars().peekable();
+    let letter = chars.next()?;
+    let mut index = match letter.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    } as i32;
+
+    match chars.peek() {
+        Some('#') => {
+            index += 1;
+            chars.next();
+        }
+        Some('b') => {
+            index -= 1;
+            chars.next();
+        }
+        _ => {}
+    }
+
+    let mut octave: i32 = chars.collect::<String>().parse().ok()?;
+    // An accidental can push the semitone across the C boundary into the
+    // neighbouring octave (e.g. Cb is the B below, B# the C above).
+    if index < 0 {
+        octave -= 1;
+    } else if index > 11 {
+        octave += 1;
+    }
+    let index = index.rem_euclid(12) as usize;
+    Some(note_to_kc(octave, index))
+}
+
+// Plays a single `Patch` across the eight channels, with a simple allocator so
+// callers can trigger notes without tracking which channel is free.
+pub struct Synth<'a> {
+    ym: &'a mut Ym2151,
+    channels: [bool; NUM_CHANNELS as usize],
+}
+
+impl<'a> Synth<'a> {
+    // Create a synth with `patch` loaded onto every channel.
+    pub fn new(ym: &'a mut Ym2151, patch: &Patch) -> Self {
+        for ch in 0..NUM_CHANNELS {
+            ym.write_register(0x08, ch); // key off
+            patch.apply(ym, ch);
+        }
+        Self {
+            ym,
+            channels: [false; NUM_CHANNELS as usize],
+        }
+    }
+
+    // Trigger `note` (a scientific note name) on `channel`.
+    pub fn key_on(&mut self, channel: u8, note: &str) {
+        if let Some(kc) = parse_note(note) {
+            self.ym.write_register(0x28 + channel, kc);
+            self.ym.write_register(0x30 + channel, 0x00); // no fine tuning
+            self.ym.write_register(0x08, 0x78 | channel); // all four slots on
+            self.channels[channel as usize] = true;
+        }
+    }
+
+    // Release whatever is playing on `channel`.
+    pub fn key_off(&mut self, channel: u8) {
+        self.ym.write_register(0x08, channel); // all slots off
+        self.channels[channel as usize] = false;
+    }
+
+    // Allocate a free channel and trigger `note` on it, returning the channel.
+    // Returns `None` if all eight channels are busy.
+    pub fn note_on(&mut self, note: &str) -> Option<u8> {
+        let channel = (0..NUM_CHANNELS).find(|&ch| !self.channels[ch as usize])?;
+        self.key_on(channel, note);
+        Some(channel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn db_to_gain_round_trip() {
+        assert!((db_to_gain(0.0) - 1.0).abs() < 1e-6);
+        assert!((db_to_gain(-6.0) - 0.501187).abs() < 1e-4);
+    }
+
+    #[test]
+    fn gain_to_tl_endpoints() {
+        assert_eq!(gain_to_tl(1.0), 0); // full scale -> loudest
+        assert_eq!(gain_to_tl(0.0), 127); // silence -> fully attenuated
+    }
+
+    #[test]
+    fn note_to_kc_matches_a4() {
+        // A4 is the demo's reference pitch, register value 0x4A.
+        assert_eq!(note_to_kc(4, 9), 0x4A);
+    }
+
+    #[test]
+    fn parse_note_handles_accidentals() {
+        assert_eq!(parse_note("A4"), Some(0x4A));
+        assert_eq!(parse_note("C#5"), parse_note("Db5"));
+        assert_eq!(parse_note("H4"), None);
+    }
+
+    #[test]
+    fn parse_note_accidentals_cross_octave_boundary() {
+        // Cb4 is the B below C4; B#4 is the C above B4.
+        assert_eq!(parse_note("Cb4"), parse_note("B3"));
+        assert_eq!(parse_note("B#4"), parse_note("C5"));
+    }
+}